@@ -9,12 +9,151 @@ pub const ALIGN_4KIB: u64 = 0x0000_1000;
 pub const ALIGN_2MIB: u64 = 0x0020_0000;
 pub const ALIGN_1GIB: u64 = 0x4000_0000;
 
+/// The width, in bits, of a canonical aarch64 virtual address (bit 47 plus the 47 bits
+/// below it); bits 48..64 are its sign extension. Used to clamp `VirtAddr::index_for`'s
+/// top-level index so it doesn't fold sign-extension bits into the index.
+const VA_BITS: u32 = 48;
+
+/// A validated power-of-two alignment.
+///
+/// The free `align_up`/`align_down` functions and the `Address::align_up`/`align_down`/
+/// `is_aligned` methods only `debug_assert!` that the alignment is a power of two, so a
+/// bad alignment silently produces garbage in release builds. Constructing an `Align`
+/// checks the invariant once, so code that is generic over `impl Into<Align>` only ever
+/// sees a value that is guaranteed valid.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Align(u64);
+
+impl Align {
+    /// A 4 KiB alignment.
+    pub const ALIGN_4KIB: Align = Align(ALIGN_4KIB);
+    /// A 2 MiB alignment.
+    pub const ALIGN_2MIB: Align = Align(ALIGN_2MIB);
+    /// A 1 GiB alignment.
+    pub const ALIGN_1GIB: Align = Align(ALIGN_1GIB);
+
+    /// Creates a new `Align`, or returns `None` if `align` is not a power of two.
+    pub const fn new(align: u64) -> Option<Align> {
+        if align.is_power_of_two() {
+            Some(Align(align))
+        } else {
+            None
+        }
+    }
+
+    /// Creates a new `Align` without checking that `align` is a power of two.
+    ///
+    /// Calling this with a value that is not a power of two is a logic error and will
+    /// produce an `Align` that breaks the invariant the rest of this module relies on.
+    pub const fn new_unchecked(align: u64) -> Align {
+        Align(align)
+    }
+
+    /// Returns the alignment as a `u64`.
+    pub const fn as_u64(self) -> u64 {
+        self.0
+    }
+}
+
+/// Converts a raw `u64` into an `Align`, for source compatibility with code that still
+/// passes an alignment as a plain integer.
+///
+/// Only debug-asserts the power-of-two invariant, matching the previous behaviour of the
+/// free `align_up`/`align_down` functions; prefer `Align::new` for a checked conversion.
+impl From<u64> for Align {
+    fn from(align: u64) -> Self {
+        debug_assert!(align.is_power_of_two(), "`align` must be a power of two");
+        Align(align)
+    }
+}
+
+/// Converts a raw `u8` into an `Align`, for source compatibility with callers that pass
+/// a narrower alignment type than `u64` (e.g. `addr.align_up(16u8)` still compiles).
+impl From<u8> for Align {
+    fn from(align: u8) -> Self {
+        Align::from(u64::from(align))
+    }
+}
+
+/// Converts a raw `u16` into an `Align`, for source compatibility with callers that pass
+/// a narrower alignment type than `u64`.
+impl From<u16> for Align {
+    fn from(align: u16) -> Self {
+        Align::from(u64::from(align))
+    }
+}
+
+/// Converts a raw `u32` into an `Align`, for source compatibility with callers that pass
+/// a narrower alignment type than `u64`.
+impl From<u32> for Align {
+    fn from(align: u32) -> Self {
+        Align::from(u64::from(align))
+    }
+}
+
+/// The MMU translation granule size, configured per translation regime via
+/// `TCR_EL1.TG0`/`TG1`.
+///
+/// The granule controls both the width of the page offset and the width of each
+/// table-level index extracted from a virtual address.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Granule {
+    /// 4 KiB granule: 12-bit page offset, 9-bit indices, 4 table levels.
+    Size4KiB,
+    /// 16 KiB granule: 14-bit page offset, 11-bit indices, 4 table levels (the top level
+    /// only uses 1 bit of its index for a 48-bit virtual address space).
+    Size16KiB,
+    /// 64 KiB granule: 16-bit page offset, 13-bit indices, 3 table levels.
+    Size64KiB,
+}
+
+impl Granule {
+    /// Returns the size in bytes of the smallest page for this granule.
+    pub const fn size(self) -> u64 {
+        1 << self.offset_bits()
+    }
+
+    /// Returns the width, in bits, of the page offset for this granule.
+    pub const fn offset_bits(self) -> u32 {
+        match self {
+            Granule::Size4KiB => 12,
+            Granule::Size16KiB => 14,
+            Granule::Size64KiB => 16,
+        }
+    }
+
+    /// Returns the width, in bits, of each table-level index for this granule.
+    pub const fn index_bits(self) -> u32 {
+        match self {
+            Granule::Size4KiB => 9,
+            Granule::Size16KiB => 11,
+            Granule::Size64KiB => 13,
+        }
+    }
+
+    /// Returns the number of translation table levels walked for this granule.
+    ///
+    /// Levels are numbered starting at 0 for the level closest to the final page or block,
+    /// matching `p1_index` (level 0) through `p4_index` (level 3).
+    pub const fn levels(self) -> u32 {
+        match self {
+            Granule::Size4KiB => 4,
+            Granule::Size16KiB => 4,
+            Granule::Size64KiB => 3,
+        }
+    }
+}
+
+/// A requested translation table level does not exist for a given `Granule`.
+#[derive(Debug)]
+pub struct InvalidLevel(u32);
+
 #[derive(Clone, Copy, Debug)]
 #[repr(u8)]
 pub enum VirtAddrRange {
-    /// 0x0000000000000000 to 0x0000FFFFFFFFFFFF
+    /// 0x0000000000000000 to 0x00007FFFFFFFFFFF (bit 47 clear, sign-extends to zero).
     BottomRange = 0,
-    /// 0xFFFF000000000000 to 0xFFFFFFFFFFFFFFFF.
+    /// 0xFFFF800000000000 to 0xFFFFFFFFFFFFFFFF (bit 47 set, sign-extends to all ones).
     TopRange = 1,
 }
 
@@ -23,7 +162,7 @@ impl VirtAddrRange {
     pub fn as_offset(&self) -> u64 {
         match self {
             VirtAddrRange::BottomRange => 0,
-            VirtAddrRange::TopRange => 0xFFFF_0000_0000_0000,
+            VirtAddrRange::TopRange => 0xFFFF_8000_0000_0000,
         }
     }
 }
@@ -46,28 +185,94 @@ pub struct VirtAddr(u64);
 #[repr(transparent)]
 pub struct PhysAddr(u64);
 
+/// A common interface implemented by both `VirtAddr` and `PhysAddr`.
+///
+/// Factoring the alignment, conversion and arithmetic methods out into this trait lets
+/// generic code, such as a page-frame allocator or table walker, be written once over
+/// `A: Address` instead of being duplicated per address kind. `VirtAddr` and `PhysAddr`
+/// also carry thin inherent forwarders for these methods, so existing callers that
+/// don't `use` this trait keep compiling.
+pub trait Address: Copy + Ord {
+    /// Converts the address to a `u64`.
+    fn as_u64(self) -> u64;
+
+    /// Creates an address from a `u64`, without performing any validity checks.
+    fn new_unchecked(addr: u64) -> Self;
+
+    /// Converts the address to a `usize`.
+    fn as_usize(self) -> usize {
+        cast::usize(self.as_u64())
+    }
+
+    /// Aligns the address upwards to the given alignment.
+    ///
+    /// See the `align_up` function for more information.
+    fn align_up<A: Into<Align>>(self, align: A) -> Self {
+        Self::new_unchecked(align_up(self.as_u64(), align.into().as_u64()))
+    }
+
+    /// Aligns the address downwards to the given alignment.
+    ///
+    /// See the `align_down` function for more information.
+    fn align_down<A: Into<Align>>(self, align: A) -> Self {
+        Self::new_unchecked(align_down(self.as_u64(), align.into().as_u64()))
+    }
+
+    /// Checks whether the address has the demanded alignment.
+    fn is_aligned<A: Into<Align>>(self, align: A) -> bool {
+        self.align_down(align.into()).as_u64() == self.as_u64()
+    }
+
+    /// Converts the address to a raw pointer.
+    #[cfg(target_pointer_width = "64")]
+    fn as_ptr<T>(self) -> *const T {
+        cast::usize(self.as_u64()) as *const T
+    }
+
+    /// Converts the address to a mutable raw pointer.
+    #[cfg(target_pointer_width = "64")]
+    fn as_mut_ptr<T>(self) -> *mut T {
+        self.as_ptr::<T>() as *mut T
+    }
+}
+
 /// A passed `u64` was not a valid virtual address.
 ///
-/// This means that bits 48 to 64 are not
-/// a valid sign extension and are not null either. So automatic sign extension would have
-/// overwritten possibly meaningful bits. This likely indicates a bug, for example an invalid
-/// address calculation.
+/// This means that bit 47 is not correctly sign-extended into bits 48..64 (the stored
+/// value is the 17-bit `addr.get_bits(47..64)` that failed the check), so automatic
+/// sign extension would have overwritten possibly meaningful bits. This likely
+/// indicates a bug, for example an invalid address calculation.
 #[derive(Debug)]
 pub struct VirtAddrNotValid(u64);
 
 impl VirtAddr {
-    /// Creates a new canonical virtual address.
+    /// Creates a new canonical virtual address, sign-extending bit 47 into bits 48..64
+    /// so that the result always falls into the bottom or top `VirtAddrRange`.
+    ///
+    /// Use `new_tbi` instead if the translation regime has Top-Byte-Ignore
+    /// (`TCR_ELx.TBI`) enabled and `addr` may carry a pointer tag in bits 56..64.
     #[inline]
     pub fn new(addr: u64) -> VirtAddr {
-        // Self::try_new(addr).expect("invalid virtual address")
-        VirtAddr(addr)
+        VirtAddr(canonicalise(addr))
+    }
+
+    /// Creates a new virtual address for a Top-Byte-Ignore translation regime,
+    /// sign-extending bit 47 into bits 48..56 while leaving bits 56..64 (the pointer
+    /// tag) untouched, so a tagged pointer round-trips through `as_u64` with its tag
+    /// intact.
+    #[inline]
+    pub fn new_tbi(addr: u64) -> VirtAddr {
+        VirtAddr(canonicalise_tbi(addr))
     }
 
     /// Tries to create a new canonical virtual address.
-    /// in aarch64, valid virtual address starts with 0x0000 or 0xffff.
+    ///
+    /// Fails unless bits 47..64 are all `0` or all `1`, i.e. unless bit 47 is already
+    /// correctly sign-extended into bits 48..64, matching the canonicalization `new`
+    /// performs.
     pub fn try_new(addr: u64) -> Result<VirtAddr, VirtAddrNotValid> {
-        match addr.get_bits(48..64) {
-            0 | 0xffff => Ok(VirtAddr(addr)), // address is canonical
+        match addr.get_bits(47..64) {
+            0 | 0x1_ffff => Ok(VirtAddr(addr)), // address is canonical
             other => Err(VirtAddrNotValid(other)),
         }
     }
@@ -82,55 +287,60 @@ impl VirtAddr {
         VirtAddr(0)
     }
 
+    /// Re-canonicalises this address as if it came from a Top-Byte-Ignore translation
+    /// regime, sign-extending bit 47 into bits 48..56 while preserving bits 56..64.
+    ///
+    /// This is the `canonicalise_tbi` counterpart to `new_tbi`, for addresses that were
+    /// already constructed (e.g. via `new_unchecked`) and may still carry a tag.
+    pub fn canonicalise_tbi(self) -> VirtAddr {
+        Self::new_tbi(self.0)
+    }
+
     /// Converts the address to an `u64`.
     #[inline]
     pub fn as_u64(self) -> u64 {
         self.0
     }
 
-    /// Creates a virtual address from the given pointer
-    pub fn from_ptr<T>(ptr: *const T) -> Self {
-        Self::new(cast::u64(ptr as usize))
+    /// Aligns the address upwards to the given alignment.
+    ///
+    /// See the `align_up` function for more information.
+    #[inline]
+    pub fn align_up<A: Into<Align>>(self, align: A) -> Self {
+        Address::align_up(self, align)
+    }
+
+    /// Aligns the address downwards to the given alignment.
+    ///
+    /// See the `align_down` function for more information.
+    #[inline]
+    pub fn align_down<A: Into<Align>>(self, align: A) -> Self {
+        Address::align_down(self, align)
+    }
+
+    /// Checks whether the address has the demanded alignment.
+    #[inline]
+    pub fn is_aligned<A: Into<Align>>(self, align: A) -> bool {
+        Address::is_aligned(self, align)
     }
 
     /// Converts the address to a raw pointer.
+    #[inline]
     #[cfg(target_pointer_width = "64")]
     pub fn as_ptr<T>(self) -> *const T {
-        cast::usize(self.as_u64()) as *const T
+        Address::as_ptr(self)
     }
 
     /// Converts the address to a mutable raw pointer.
+    #[inline]
     #[cfg(target_pointer_width = "64")]
     pub fn as_mut_ptr<T>(self) -> *mut T {
-        self.as_ptr::<T>() as *mut T
+        Address::as_mut_ptr(self)
     }
 
-    /// Aligns the virtual address upwards to the given alignment.
-    ///
-    /// See the `align_up` function for more information.
-    pub fn align_up<U>(self, align: U) -> Self
-    where
-        U: Into<u64>,
-    {
-        VirtAddr(align_up(self.0, align.into()))
-    }
-
-    /// Aligns the virtual address downwards to the given alignment.
-    ///
-    /// See the `align_down` function for more information.
-    pub fn align_down<U>(self, align: U) -> Self
-    where
-        U: Into<u64>,
-    {
-        VirtAddr(align_down(self.0, align.into()))
-    }
-
-    /// Checks whether the virtual address has the demanded alignment.
-    pub fn is_aligned<U>(self, align: U) -> bool
-    where
-        U: Into<u64>,
-    {
-        self.align_down(align) == self
+    /// Creates a virtual address from the given pointer
+    pub fn from_ptr<T>(ptr: *const T) -> Self {
+        Self::new(cast::u64(ptr as usize))
     }
 
     /// Returns the 12-bit page offset of this virtual address.
@@ -140,14 +350,18 @@ impl VirtAddr {
 
     /// Returns the VA range
     pub fn va_range(&self) -> Result<VirtAddrRange, VirtAddrNotValid> {
-        match self.va_range_bits() {
-            0x0000 => Ok(VirtAddrRange::BottomRange),
-            0xffff => Ok(VirtAddrRange::TopRange),
-            _ => Err(VirtAddrNotValid(self.0)),
+        match self.0.get_bits(47..64) {
+            0 => Ok(VirtAddrRange::BottomRange),
+            0x1_ffff => Ok(VirtAddrRange::TopRange),
+            other => Err(VirtAddrNotValid(other)),
         }
     }
 
-    /// Returns the top 16 bits
+    /// Returns bits 48..64 of this address, as a raw `u16`.
+    ///
+    /// Note this is one bit narrower than the 17-bit `get_bits(47..64)` that `va_range`
+    /// and `try_new` check against; it does not by itself tell you whether the address
+    /// is canonical.
     pub fn va_range_bits(&self) -> u16 {
         ((self.0 >> 48) & 0xffff) as u16
     }
@@ -175,6 +389,44 @@ impl VirtAddr {
     pub fn p4_index(&self) -> u9 {
         u9::new(((self.0 >> 12 >> 9 >> 9 >> 9) & 0o777).try_into().unwrap())
     }
+
+    /// Returns the page offset of this address for the given translation `granule`.
+    ///
+    /// `p1_index`..`p4_index` assume a 4 KiB granule; use this together with `index_for`
+    /// on kernels configured for a 16 KiB or 64 KiB granule.
+    pub fn page_offset_for(&self, granule: Granule) -> u64 {
+        self.0 & (granule.size() - 1)
+    }
+
+    /// Returns the table index at `level` for the given translation `granule`.
+    ///
+    /// `level` is numbered starting at 0 for the level closest to the final page or block,
+    /// as with `p1_index`. Returns `Err(InvalidLevel)` if `granule` does not have that many
+    /// levels, e.g. level 3 with a 64 KiB granule, which only walks 3 levels.
+    ///
+    /// The top level's index is clamped to the bits actually covered by a 48-bit
+    /// virtual address space, so it doesn't fold the canonical sign-extension bits
+    /// into the index, e.g. a 16 KiB granule's level 3 index is only 1 bit wide.
+    pub fn index_for(&self, level: u32, granule: Granule) -> Result<u32, InvalidLevel> {
+        if level >= granule.levels() {
+            return Err(InvalidLevel(level));
+        }
+        let index_bits = granule.index_bits();
+        let shift = granule.offset_bits() + level * index_bits;
+        let width = index_bits.min(VA_BITS.saturating_sub(shift));
+        let mask = (1u64 << width) - 1;
+        Ok(((self.0 >> shift) & mask) as u32)
+    }
+}
+
+impl Address for VirtAddr {
+    fn as_u64(self) -> u64 {
+        self.0
+    }
+
+    fn new_unchecked(addr: u64) -> Self {
+        VirtAddr(addr)
+    }
 }
 
 impl fmt::Debug for VirtAddr {
@@ -242,6 +494,55 @@ impl Sub<VirtAddr> for VirtAddr {
     }
 }
 
+impl VirtAddr {
+    /// Returns an iterator over the addresses in `[start, end)`, advancing by `stride`
+    /// bytes each step (e.g. `ALIGN_4KIB`/`ALIGN_2MIB`/`ALIGN_1GIB` to walk page by page).
+    ///
+    /// Iteration correctly skips the non-canonical hole: stepping past the top of the
+    /// bottom range (`0x0000_7fff_ffff_ffff`) continues at the bottom of the top range
+    /// (`0xffff_8000_0000_0000`) instead of yielding non-canonical addresses.
+    pub fn range(start: VirtAddr, end: VirtAddr, stride: u64) -> VirtAddrRangeIter {
+        VirtAddrRangeIter {
+            next: Some(start),
+            end,
+            stride,
+        }
+    }
+}
+
+/// An iterator over a range of `VirtAddr`, produced by `VirtAddr::range`.
+#[derive(Clone, Debug)]
+pub struct VirtAddrRangeIter {
+    next: Option<VirtAddr>,
+    end: VirtAddr,
+    stride: u64,
+}
+
+impl Iterator for VirtAddrRangeIter {
+    type Item = VirtAddr;
+
+    fn next(&mut self) -> Option<VirtAddr> {
+        let current = self.next?;
+        if current >= self.end {
+            self.next = None;
+            return None;
+        }
+
+        let mut stepped = current.as_u64().wrapping_add(self.stride);
+        if stepped > 0x0000_7fff_ffff_ffff && stepped < 0xffff_8000_0000_0000 {
+            // Skip the non-canonical hole between the bottom and top ranges.
+            stepped = 0xffff_8000_0000_0000;
+        }
+
+        self.next = if stepped <= current.as_u64() {
+            None // wrapped around
+        } else {
+            Some(VirtAddr::new_unchecked(stepped))
+        };
+        Some(current)
+    }
+}
+
 /// A passed `u64` was not a valid physical address.
 ///
 /// This means that bits 52 to 64 are not were not all null.
@@ -279,32 +580,50 @@ impl PhysAddr {
         self.0 == 0
     }
 
-    /// Aligns the physical address upwards to the given alignment.
+    /// Aligns the address upwards to the given alignment.
     ///
     /// See the `align_up` function for more information.
-    pub fn align_up<U>(self, align: U) -> Self
-    where
-        U: Into<u64>,
-    {
-        PhysAddr(align_up(self.0, align.into()))
+    #[inline]
+    pub fn align_up<A: Into<Align>>(self, align: A) -> Self {
+        Address::align_up(self, align)
     }
 
-    /// Aligns the physical address downwards to the given alignment.
+    /// Aligns the address downwards to the given alignment.
     ///
     /// See the `align_down` function for more information.
-    pub fn align_down<U>(self, align: U) -> Self
-    where
-        U: Into<u64>,
-    {
-        PhysAddr(align_down(self.0, align.into()))
+    #[inline]
+    pub fn align_down<A: Into<Align>>(self, align: A) -> Self {
+        Address::align_down(self, align)
+    }
+
+    /// Checks whether the address has the demanded alignment.
+    #[inline]
+    pub fn is_aligned<A: Into<Align>>(self, align: A) -> bool {
+        Address::is_aligned(self, align)
+    }
+
+    /// Converts the address to a raw pointer.
+    #[inline]
+    #[cfg(target_pointer_width = "64")]
+    pub fn as_ptr<T>(self) -> *const T {
+        Address::as_ptr(self)
+    }
+
+    /// Converts the address to a mutable raw pointer.
+    #[inline]
+    #[cfg(target_pointer_width = "64")]
+    pub fn as_mut_ptr<T>(self) -> *mut T {
+        Address::as_mut_ptr(self)
+    }
+}
+
+impl Address for PhysAddr {
+    fn as_u64(self) -> u64 {
+        self.0
     }
 
-    /// Checks whether the physical address has the demanded alignment.
-    pub fn is_aligned<U>(self, align: U) -> bool
-    where
-        U: Into<u64>,
-    {
-        self.align_down(align) == self
+    fn new_unchecked(addr: u64) -> Self {
+        PhysAddr(addr)
     }
 }
 
@@ -397,6 +716,65 @@ impl Sub<PhysAddr> for PhysAddr {
     }
 }
 
+impl PhysAddr {
+    /// Returns an iterator over the addresses in `[start, end)`, advancing by `stride`
+    /// bytes each step (e.g. `ALIGN_4KIB`/`ALIGN_2MIB`/`ALIGN_1GIB` to walk frame by frame).
+    pub fn range(start: PhysAddr, end: PhysAddr, stride: u64) -> PhysAddrRangeIter {
+        PhysAddrRangeIter {
+            next: Some(start),
+            end,
+            stride,
+        }
+    }
+}
+
+/// An iterator over a range of `PhysAddr`, produced by `PhysAddr::range`.
+#[derive(Clone, Debug)]
+pub struct PhysAddrRangeIter {
+    next: Option<PhysAddr>,
+    end: PhysAddr,
+    stride: u64,
+}
+
+impl Iterator for PhysAddrRangeIter {
+    type Item = PhysAddr;
+
+    fn next(&mut self) -> Option<PhysAddr> {
+        let current = self.next?;
+        if current >= self.end {
+            self.next = None;
+            return None;
+        }
+
+        let stepped = current.as_u64().wrapping_add(self.stride);
+        self.next = if stepped <= current.as_u64() {
+            None // wrapped around
+        } else {
+            Some(PhysAddr::new(stepped))
+        };
+        Some(current)
+    }
+}
+
+/// Sign-extends bit 47 of `addr` into bits 48..64, producing a canonical aarch64
+/// virtual address (bits 48..64 are either all `0` or all `1`).
+#[inline]
+fn canonicalise(addr: u64) -> u64 {
+    ((addr << 16) as i64 >> 16) as u64
+}
+
+/// Sign-extends bit 47 of `addr` into bits 48..56 only, leaving bits 56..64 (the
+/// pointer tag) untouched, for a Top-Byte-Ignore translation regime.
+///
+/// Unlike `canonicalise`, this does not discard bits 56..64: the MMU ignores that byte
+/// for translation, but software reading the address back still sees its original tag.
+#[inline]
+fn canonicalise_tbi(addr: u64) -> u64 {
+    let tag = addr & 0xff00_0000_0000_0000;
+    let sign_extended = canonicalise(addr) & 0x00ff_ffff_ffff_ffff;
+    tag | sign_extended
+}
+
 /// Align address downwards.
 ///
 /// Returns the greatest x with alignment `align` so that x <= addr. The alignment must be
@@ -442,4 +820,137 @@ mod tests {
         assert_eq!(align_up(0, 2), 0);
         assert_eq!(align_up(0, 0x8000000000000000), 0);
     }
+
+    #[test]
+    pub fn test_address_trait_shared_impl() {
+        fn align_up_generic<A: Address>(addr: A) -> A {
+            addr.align_up(ALIGN_4KIB)
+        }
+
+        assert_eq!(align_up_generic(VirtAddr::new(0x1001)).as_u64(), 0x2000);
+        assert_eq!(align_up_generic(PhysAddr::new(0x1001)).as_u64(), 0x2000);
+        assert!(PhysAddr::new(0x1000).is_aligned(ALIGN_4KIB));
+    }
+
+    #[test]
+    pub fn test_align_rejects_non_power_of_two() {
+        assert!(Align::new(0x1000).is_some());
+        assert!(Align::new(0).is_none());
+        assert!(Align::new(3).is_none());
+    }
+
+    #[test]
+    pub fn test_align_up_accepts_align_and_u64() {
+        assert_eq!(VirtAddr::new(0x1001).align_up(Align::ALIGN_4KIB).as_u64(), 0x2000);
+        assert_eq!(VirtAddr::new(0x1001).align_up(ALIGN_4KIB).as_u64(), 0x2000);
+    }
+
+    #[test]
+    pub fn test_virt_addr_range_skips_hole() {
+        let start = VirtAddr::new(0x0000_7fff_ffff_e000);
+        let end = VirtAddr::new(0xffff_8000_0000_2000);
+        let pages: Vec<VirtAddr> = VirtAddr::range(start, end, ALIGN_4KIB).collect();
+
+        assert_eq!(
+            pages,
+            vec![
+                VirtAddr::new(0x0000_7fff_ffff_e000),
+                VirtAddr::new(0x0000_7fff_ffff_f000),
+                VirtAddr::new(0xffff_8000_0000_0000),
+                VirtAddr::new(0xffff_8000_0000_1000),
+            ]
+        );
+    }
+
+    #[test]
+    pub fn test_index_for_granules() {
+        let addr = VirtAddr::new(0x0000_ab12_3456_7890);
+
+        // 4 KiB granule matches the existing fixed-granule accessors.
+        assert_eq!(addr.page_offset_for(Granule::Size4KiB), u64::from(addr.page_offset()));
+        assert_eq!(addr.index_for(0, Granule::Size4KiB).unwrap(), u16::from(addr.p1_index()) as u32);
+        assert_eq!(addr.index_for(3, Granule::Size4KiB).unwrap(), u16::from(addr.p4_index()) as u32);
+
+        // 64 KiB granule only has 3 levels.
+        assert!(addr.index_for(3, Granule::Size64KiB).is_err());
+        assert_eq!(addr.page_offset_for(Granule::Size64KiB), addr.as_u64() & 0xffff);
+
+        // 16 KiB granule's top level only covers 1 bit of a 48-bit virtual address, so
+        // the index must be clamped rather than reading 11 bits of sign-extension.
+        assert!(addr.index_for(3, Granule::Size16KiB).unwrap() <= 1);
+    }
+
+    #[test]
+    pub fn test_canonicalise_bottom_range() {
+        // Highest address in the bottom range is left untouched.
+        assert_eq!(VirtAddr::new(0x0000_7fff_ffff_ffff).as_u64(), 0x0000_7fff_ffff_ffff);
+        // Bit 47 set but no other high bits: sign-extends into the top range.
+        assert_eq!(VirtAddr::new(0x0000_8000_0000_0000).as_u64(), 0xffff_8000_0000_0000);
+    }
+
+    #[test]
+    pub fn test_canonicalise_top_range() {
+        // Already in the top range: stays there.
+        assert_eq!(VirtAddr::new(0xffff_8000_0000_1234).as_u64(), 0xffff_8000_0000_1234);
+        // Highest address overall.
+        assert_eq!(VirtAddr::new(0xffff_ffff_ffff_ffff).as_u64(), 0xffff_ffff_ffff_ffff);
+    }
+
+    #[test]
+    pub fn test_try_new_bottom_range() {
+        // Highest address in the bottom range: accepted as-is.
+        assert_eq!(
+            VirtAddr::try_new(0x0000_7fff_ffff_ffff).unwrap().as_u64(),
+            0x0000_7fff_ffff_ffff
+        );
+        // Bit 47 set but bits 48..64 not sign-extended: not canonical.
+        assert!(VirtAddr::try_new(0x0000_8000_0000_0000).is_err());
+    }
+
+    #[test]
+    pub fn test_try_new_top_range() {
+        // Lowest address in the top range: accepted as-is.
+        assert_eq!(
+            VirtAddr::try_new(0xffff_8000_0000_0000).unwrap().as_u64(),
+            0xffff_8000_0000_0000
+        );
+        // Highest address overall.
+        assert_eq!(
+            VirtAddr::try_new(0xffff_ffff_ffff_ffff).unwrap().as_u64(),
+            0xffff_ffff_ffff_ffff
+        );
+    }
+
+    #[test]
+    pub fn test_va_range_bottom_and_top() {
+        assert!(matches!(
+            VirtAddr::new(0x0000_7fff_ffff_ffff).va_range().unwrap(),
+            VirtAddrRange::BottomRange
+        ));
+        assert!(matches!(
+            VirtAddr::new(0xffff_8000_0000_0000).va_range().unwrap(),
+            VirtAddrRange::TopRange
+        ));
+    }
+
+    #[test]
+    pub fn test_va_range_rejects_non_canonical_boundary() {
+        // Bit 47 set but no other high bits: not canonical (the old x86-style boundary
+        // at 0xFFFF_0000_0000_0000 would have wrongly accepted this as a valid address).
+        assert!(VirtAddr::new_unchecked(0x0000_8000_0000_0000).va_range().is_err());
+    }
+
+    #[test]
+    pub fn test_new_tbi_preserves_tag() {
+        // A tagged pointer in the bottom range: the tag in bits 56..64 survives, unlike
+        // plain `new`, which sign-extends it away.
+        let tagged = 0x4200_0000_1234_5678;
+        assert_eq!(VirtAddr::new_tbi(tagged).as_u64(), 0x4200_0000_1234_5678);
+        assert_eq!(VirtAddr::new(tagged).as_u64(), 0x0000_0000_1234_5678);
+
+        // A tagged pointer in the top range.
+        let tagged_top = 0xab00_8000_0000_0000;
+        assert_eq!(VirtAddr::new_tbi(tagged_top).as_u64(), 0xabff_8000_0000_0000);
+        assert_eq!(VirtAddr::new(tagged_top).as_u64(), 0xffff_8000_0000_0000);
+    }
 }
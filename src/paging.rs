@@ -0,0 +1,478 @@
+//! ARMv8-A stage-1 VMSAv8-64 translation tables.
+//!
+//! This models the 64-bit page/block descriptor format used by the MMU when walking the
+//! tables pointed to by `TTBR0_EL1`/`TTBR1_EL1`, built on top of the `p1_index`..`p4_index`
+//! accessors on `VirtAddr`.
+
+use core::fmt;
+use core::ops::{Index, IndexMut};
+
+use ux::u9;
+
+use crate::addr::{Address, PhysAddr, VirtAddr, ALIGN_4KIB};
+
+/// The number of entries in a translation table at any level.
+pub const ENTRY_COUNT: usize = 512;
+
+/// Bitmask for the output address field, bits `[47:12]`.
+const ADDR_MASK: u64 = 0x0000_ffff_ffff_f000;
+
+bitflags::bitflags! {
+    /// Attribute and descriptor-type bits of a translation table descriptor.
+    ///
+    /// These cover bits `[1:0]` (descriptor type), the lower attributes `[11:2]` and the
+    /// upper attributes `[63:50]` of a VMSAv8-64 page, block or table descriptor, except
+    /// the `SH[9:8]` field, which is read and written through `Shareability` instead.
+    pub struct PageTableFlags: u64 {
+        /// Bit `[0]`. Must be set for the descriptor to be valid.
+        const VALID = 1 << 0;
+        /// Bit `[1]`. Clear for a block descriptor, set for a table or page descriptor.
+        const TABLE_OR_PAGE = 1 << 1;
+
+        /// Bit `[2]` of the `AttrIndx` field, indexing into `MAIR_EL1`.
+        const ATTR_INDX_0 = 1 << 2;
+        /// Bit `[3]` of the `AttrIndx` field, indexing into `MAIR_EL1`.
+        const ATTR_INDX_1 = 1 << 3;
+        /// Bit `[4]` of the `AttrIndx` field, indexing into `MAIR_EL1`.
+        const ATTR_INDX_2 = 1 << 4;
+
+        /// Bit `[5]`. Non-secure bit, only meaningful at Secure EL1/EL0.
+        const NS = 1 << 5;
+
+        /// Bit `[6]` of the access permission field. If set, EL0 may access this region.
+        const AP_EL0 = 1 << 6;
+        /// Bit `[7]` of the access permission field. If set, this region is read-only.
+        const AP_RO = 1 << 7;
+
+        /// Bit `[10]`. Access flag, must be set or the first access faults.
+        const AF = 1 << 10;
+
+        /// Bit `[11]`. Not-global bit.
+        const NG = 1 << 11;
+
+        /// Bit `[53]`. Privileged execute-never.
+        const PXN = 1 << 53;
+        /// Bit `[54]`. Unprivileged execute-never.
+        const UXN = 1 << 54;
+    }
+}
+
+/// The two-bit `SH[9:8]` shareability field of a translation table descriptor.
+///
+/// Modeled as an enum rather than two independent bitflags because only three of the
+/// four bit patterns are architecturally defined: `00` is Non-shareable, `10` is Outer
+/// Shareable and `11` is Inner Shareable, while `01` is reserved. Flags named `SH_INNER`/
+/// `SH_OUTER` for bits `[8]`/`[9]` invited a caller to set `SH_INNER` alone expecting
+/// inner-shareable memory and land on the reserved encoding instead.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Shareability {
+    /// `00`: Non-shareable.
+    NonShareable,
+    /// `10`: Outer Shareable.
+    OuterShareable,
+    /// `11`: Inner Shareable.
+    InnerShareable,
+}
+
+impl Shareability {
+    const BIT0: u64 = 1 << 8;
+    const BIT1: u64 = 1 << 9;
+    const MASK: u64 = Self::BIT0 | Self::BIT1;
+
+    /// Encodes this shareability as bits `[9:8]`.
+    const fn bits(self) -> u64 {
+        match self {
+            Shareability::NonShareable => 0,
+            Shareability::OuterShareable => Self::BIT1,
+            Shareability::InnerShareable => Self::BIT0 | Self::BIT1,
+        }
+    }
+
+    /// Decodes bits `[9:8]` of `bits`, or `None` for the reserved `01` encoding.
+    fn from_bits(bits: u64) -> Option<Self> {
+        match bits & Self::MASK {
+            0 => Some(Shareability::NonShareable),
+            Self::BIT1 => Some(Shareability::OuterShareable),
+            Self::MASK => Some(Shareability::InnerShareable),
+            _ => None,
+        }
+    }
+}
+
+/// A single 64-bit translation table descriptor.
+#[derive(Clone)]
+#[repr(transparent)]
+pub struct PageTableEntry(u64);
+
+impl PageTableEntry {
+    /// Creates an unused page table entry.
+    #[inline]
+    pub const fn new() -> Self {
+        PageTableEntry(0)
+    }
+
+    /// Returns whether this entry does not yet point to anything.
+    #[inline]
+    pub fn is_unused(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Clears this entry.
+    #[inline]
+    pub fn set_unused(&mut self) {
+        self.0 = 0;
+    }
+
+    /// Returns whether the `VALID` bit is set, i.e. whether this descriptor is a valid
+    /// block, table or page descriptor.
+    #[inline]
+    pub fn is_valid(&self) -> bool {
+        self.flags().contains(PageTableFlags::VALID)
+    }
+
+    /// Returns the flags of this entry.
+    #[inline]
+    pub fn flags(&self) -> PageTableFlags {
+        PageTableFlags::from_bits_truncate(self.0)
+    }
+
+    /// Returns the physical output address this descriptor points to.
+    ///
+    /// The returned address is meaningless if `is_valid()` is `false`.
+    #[inline]
+    pub fn addr(&self) -> PhysAddr {
+        PhysAddr::new(self.0 & ADDR_MASK)
+    }
+
+    /// Sets the output address and flags of this entry.
+    ///
+    /// Panics if `addr` is not 4 KiB aligned.
+    #[inline]
+    pub fn set_addr(&mut self, addr: PhysAddr, flags: PageTableFlags) {
+        assert!(addr.is_aligned(ALIGN_4KIB));
+        self.0 = (addr.as_u64() & ADDR_MASK) | flags.bits();
+    }
+
+    /// Replaces the flags of this entry, leaving the output address untouched.
+    #[inline]
+    pub fn set_flags(&mut self, flags: PageTableFlags) {
+        self.0 = (self.0 & ADDR_MASK) | flags.bits();
+    }
+
+    /// Returns the shareability field of this entry, or `None` if it holds the reserved
+    /// `01` encoding.
+    #[inline]
+    pub fn shareability(&self) -> Option<Shareability> {
+        Shareability::from_bits(self.0)
+    }
+
+    /// Sets the shareability field of this entry, leaving the output address and the
+    /// other flags untouched.
+    #[inline]
+    pub fn set_shareability(&mut self, shareability: Shareability) {
+        self.0 = (self.0 & !Shareability::MASK) | shareability.bits();
+    }
+}
+
+impl fmt::Debug for PageTableEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut s = f.debug_struct("PageTableEntry");
+        s.field("addr", &self.addr());
+        s.field("flags", &self.flags());
+        s.finish()
+    }
+}
+
+impl Default for PageTableEntry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A 512-entry, 4 KiB aligned aarch64 translation table.
+#[repr(C, align(4096))]
+pub struct PageTable {
+    entries: [PageTableEntry; ENTRY_COUNT],
+}
+
+impl PageTable {
+    /// Creates an empty translation table, with every entry marked unused.
+    #[inline]
+    pub const fn new() -> Self {
+        const EMPTY: PageTableEntry = PageTableEntry::new();
+        PageTable {
+            entries: [EMPTY; ENTRY_COUNT],
+        }
+    }
+
+    /// Clears all entries of this table.
+    #[inline]
+    pub fn zero(&mut self) {
+        for entry in self.entries.iter_mut() {
+            entry.set_unused();
+        }
+    }
+
+    /// Returns an iterator over the entries of this table.
+    pub fn iter(&self) -> impl Iterator<Item = &PageTableEntry> {
+        self.entries.iter()
+    }
+
+    /// Returns a mutable iterator over the entries of this table.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut PageTableEntry> {
+        self.entries.iter_mut()
+    }
+
+    /// Returns a mutable reference to the entry at the given index, as produced by
+    /// `VirtAddr::p1_index`..`p4_index`.
+    #[inline]
+    pub fn index_mut(&mut self, index: u9) -> &mut PageTableEntry {
+        &mut self.entries[u16::from(index) as usize]
+    }
+}
+
+impl Default for PageTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Index<u9> for PageTable {
+    type Output = PageTableEntry;
+
+    #[inline]
+    fn index(&self, index: u9) -> &Self::Output {
+        &self.entries[u16::from(index) as usize]
+    }
+}
+
+impl IndexMut<u9> for PageTable {
+    #[inline]
+    fn index_mut(&mut self, index: u9) -> &mut Self::Output {
+        &mut self.entries[u16::from(index) as usize]
+    }
+}
+
+impl Index<usize> for PageTable {
+    type Output = PageTableEntry;
+
+    #[inline]
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.entries[index]
+    }
+}
+
+impl IndexMut<usize> for PageTable {
+    #[inline]
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        &mut self.entries[index]
+    }
+}
+
+/// A page-aligned, fixed-size chunk of virtual memory, e.g. a 4 KiB, 2 MiB or 1 GiB page.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Page {
+    start_address: VirtAddr,
+    size: u64,
+}
+
+impl Page {
+    /// Returns the page of the given `size` that contains `address`.
+    pub fn containing_address(address: VirtAddr, size: u64) -> Self {
+        Page {
+            start_address: address.align_down(size),
+            size,
+        }
+    }
+
+    /// Returns the start address of this page.
+    pub fn start_address(&self) -> VirtAddr {
+        self.start_address
+    }
+
+    /// Returns the size, in bytes, of this page.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+}
+
+/// An iterator over a range of equally-sized `Page`s, produced by `PageRange::new`.
+///
+/// Mirrors `VirtAddr::range`, stepping one page at a time and skipping the
+/// non-canonical hole between the bottom and top `VirtAddr` ranges.
+#[derive(Clone, Debug)]
+pub struct PageRange {
+    next: Option<Page>,
+    end: VirtAddr,
+}
+
+impl PageRange {
+    /// Creates an iterator over the `size`-sized pages in `[start, end)`.
+    pub fn new(start: VirtAddr, end: VirtAddr, size: u64) -> Self {
+        PageRange {
+            next: Some(Page::containing_address(start, size)),
+            end,
+        }
+    }
+}
+
+impl Iterator for PageRange {
+    type Item = Page;
+
+    fn next(&mut self) -> Option<Page> {
+        let page = self.next?;
+        if page.start_address() >= self.end {
+            self.next = None;
+            return None;
+        }
+
+        let mut stepped = page.start_address().as_u64().wrapping_add(page.size());
+        if stepped > 0x0000_7fff_ffff_ffff && stepped < 0xffff_8000_0000_0000 {
+            // Skip the non-canonical hole between the bottom and top ranges.
+            stepped = 0xffff_8000_0000_0000;
+        }
+
+        self.next = if stepped <= page.start_address().as_u64() {
+            None // wrapped around
+        } else {
+            Some(Page {
+                start_address: VirtAddr::new_unchecked(stepped),
+                size: page.size,
+            })
+        };
+        Some(page)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::addr::{ALIGN_1GIB, ALIGN_2MIB};
+
+    #[test]
+    pub fn test_entry_set_addr_round_trip() {
+        let mut entry = PageTableEntry::new();
+        let flags = PageTableFlags::VALID | PageTableFlags::TABLE_OR_PAGE | PageTableFlags::AF;
+        entry.set_addr(PhysAddr::new(0x1234_5000), flags);
+
+        assert_eq!(entry.addr(), PhysAddr::new(0x1234_5000));
+        assert_eq!(entry.flags(), flags);
+    }
+
+    #[test]
+    pub fn test_entry_set_addr_masks_out_of_range_bits() {
+        let mut entry = PageTableEntry::new();
+        // Bits above [47:12] are not part of the output address field and must be
+        // dropped rather than folded into the stored address.
+        entry.set_addr(PhysAddr::new(0x0001_0000_1234_5000), PageTableFlags::empty());
+        assert_eq!(entry.addr(), PhysAddr::new(0x1234_5000));
+    }
+
+    #[test]
+    #[should_panic]
+    pub fn test_entry_set_addr_panics_on_misaligned_address() {
+        let mut entry = PageTableEntry::new();
+        entry.set_addr(PhysAddr::new(0x1234_5001), PageTableFlags::empty());
+    }
+
+    #[test]
+    pub fn test_entry_is_valid_is_unused_transitions() {
+        let mut entry = PageTableEntry::new();
+        assert!(entry.is_unused());
+        assert!(!entry.is_valid());
+
+        entry.set_addr(PhysAddr::new(0x1000), PageTableFlags::VALID);
+        assert!(!entry.is_unused());
+        assert!(entry.is_valid());
+
+        entry.set_flags(PageTableFlags::empty());
+        assert!(!entry.is_valid());
+
+        entry.set_unused();
+        assert!(entry.is_unused());
+    }
+
+    #[test]
+    pub fn test_entry_shareability_round_trip() {
+        let mut entry = PageTableEntry::new();
+        assert_eq!(entry.shareability(), Some(Shareability::NonShareable));
+
+        entry.set_shareability(Shareability::InnerShareable);
+        assert_eq!(entry.shareability(), Some(Shareability::InnerShareable));
+
+        entry.set_shareability(Shareability::OuterShareable);
+        assert_eq!(entry.shareability(), Some(Shareability::OuterShareable));
+    }
+
+    #[test]
+    pub fn test_entry_shareability_reserved_encoding() {
+        let mut entry = PageTableEntry::new();
+        entry.set_shareability(Shareability::InnerShareable);
+        // Poke the reserved `01` encoding directly; there is no safe API to construct it.
+        entry.0 = (entry.0 & !Shareability::MASK) | Shareability::BIT0;
+        assert_eq!(entry.shareability(), None);
+    }
+
+    #[test]
+    pub fn test_table_index_mut_and_index_agree() {
+        let mut table = PageTable::new();
+        let index = u9::new(3);
+
+        table.index_mut(index).set_addr(PhysAddr::new(0x2000), PageTableFlags::VALID);
+
+        assert_eq!(table[index].addr(), PhysAddr::new(0x2000));
+        assert_eq!(table[3usize].addr(), PhysAddr::new(0x2000));
+
+        table[3usize].set_unused();
+        assert!(table.index_mut(index).is_unused());
+    }
+
+    #[test]
+    #[should_panic]
+    pub fn test_table_index_out_of_bounds_panics() {
+        let table = PageTable::new();
+        let _ = &table[ENTRY_COUNT];
+    }
+
+    #[test]
+    pub fn test_page_range_in_range_count() {
+        let start = VirtAddr::new(0x1000);
+        let end = VirtAddr::new(0x1000 + 4 * ALIGN_4KIB);
+        let pages: Vec<Page> = PageRange::new(start, end, ALIGN_4KIB).collect();
+        assert_eq!(pages.len(), 4);
+    }
+
+    #[test]
+    pub fn test_page_range_skips_hole_for_2mib_pages() {
+        let start = VirtAddr::new(0x0000_7fff_ffe0_0000);
+        let end = VirtAddr::new(0xffff_8000_0020_0000);
+        let pages: Vec<VirtAddr> = PageRange::new(start, end, ALIGN_2MIB)
+            .map(|page| page.start_address())
+            .collect();
+
+        assert_eq!(
+            pages,
+            vec![
+                VirtAddr::new(0x0000_7fff_ffe0_0000),
+                VirtAddr::new(0xffff_8000_0000_0000),
+            ]
+        );
+    }
+
+    #[test]
+    pub fn test_page_range_skips_hole_for_1gib_pages() {
+        let start = VirtAddr::new(0x0000_7fff_c000_0000);
+        let end = VirtAddr::new(0xffff_8000_4000_0000);
+        let pages: Vec<VirtAddr> = PageRange::new(start, end, ALIGN_1GIB)
+            .map(|page| page.start_address())
+            .collect();
+
+        assert_eq!(
+            pages,
+            vec![
+                VirtAddr::new(0x0000_7fff_c000_0000),
+                VirtAddr::new(0xffff_8000_0000_0000),
+            ]
+        );
+    }
+}